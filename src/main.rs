@@ -1,12 +1,17 @@
-use rand::{self, Rng};
 use tetra::audio::Sound;
-use tetra::graphics::ScreenScaling;
-use tetra::graphics::{self, Color, DrawParams, Font, Text, Texture, Rectangle, Vec2};
+use tetra::graphics::{self, Canvas, Color, DrawParams, FilterMode, Font, Text, Texture, Rectangle, Vec2};
 use tetra::graphics::animation::Animation;
-use tetra::input::{self, Key, MouseButton};
+use tetra::input::{self, GamepadAxis, GamepadButton, Key, MouseButton};
+use tetra::time;
 use tetra::window;
-use tetra::{Context, ContextBuilder, State};
+use tetra::{Context, ContextBuilder, Event, State};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
 use std::f64;
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 const SCREEN_WIDTH: i32 = 288;
 const SCREEN_HEIGHT: i32 = 505;
@@ -14,7 +19,7 @@ const GRAVITY: f32 = 9.1;
 
 fn main() -> tetra::Result {
     ContextBuilder::new("Flappy Bird", SCREEN_WIDTH, SCREEN_HEIGHT)
-        .resizable(false)
+        .resizable(true)
         .quit_on_escape(true)
         .build()?
         .run_with(SceneManager::new)
@@ -27,14 +32,426 @@ trait Tweenable {
     fn update(&mut self, delta: f64);
 }
 
+#[derive(Clone, Copy)]
+enum Easing {
+    Linear,
+    QuadIn,
+    QuadOut,
+    CubicInOut,
+    ElasticOut,
+}
+
+impl Easing {
+    fn apply(self, t: f64) -> f64 {
+        match self {
+            Easing::Linear => t,
+            Easing::QuadIn => t * t,
+            Easing::QuadOut => t * (2.0 - t),
+            Easing::CubicInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+            // Overshoots past `end` before settling - used for the flap "pop".
+            Easing::ElasticOut => {
+                if t <= 0.0 || t >= 1.0 {
+                    t
+                } else {
+                    let c4 = (2.0 * f64::consts::PI) / 3.0;
+                    2f64.powf(-10.0 * t) * ((t * 10.0 - 0.75) * c4).sin() + 1.0
+                }
+            }
+        }
+    }
+}
+
+struct Tween<T> {
+    start: T,
+    end: T,
+    duration: f64,
+    elapsed: f64,
+    easing: Easing,
+    value: T,
+}
+
+impl Tween<f32> {
+    fn new(start: f32, end: f32, duration: f64, easing: Easing) -> Tween<f32> {
+        Tween {
+            start,
+            end,
+            duration,
+            elapsed: 0.0,
+            easing,
+            value: start,
+        }
+    }
+
+    fn value(&self) -> f32 {
+        self.value
+    }
+}
+
+impl Tweenable for Tween<f32> {
+    fn is_complete(&mut self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    fn update(&mut self, delta: f64) {
+        self.elapsed = (self.elapsed + delta).min(self.duration);
+        let t = if self.duration > 0.0 {
+            self.elapsed / self.duration
+        } else {
+            1.0
+        };
+        self.value = self.start + (self.end - self.start) * self.easing.apply(t) as f32;
+    }
+}
+
 struct TweenManager {
+    tweens: Vec<Rc<RefCell<dyn Tweenable>>>,
+}
+
+impl TweenManager {
+    fn new() -> TweenManager {
+        TweenManager { tweens: Vec::new() }
+    }
+
+    fn add(&mut self, tween: Rc<RefCell<dyn Tweenable>>) {
+        self.tweens.push(tween);
+    }
+
+    fn update(&mut self, delta: f64) {
+        for tween in self.tweens.iter() {
+            tween.borrow_mut().update(delta);
+        }
+        self.tweens.retain(|tween| !tween.borrow_mut().is_complete());
+    }
+}
+
+// === RNG ===
+
+// A Cave Story-style xorshift generator. Unlike `rand::thread_rng`, a
+// `XorShift` with a given seed always produces the same sequence, so a
+// seed can be shared and replayed.
+struct XorShift {
+    state: u32,
+}
+
+impl XorShift {
+    fn new(seed: u32) -> XorShift {
+        XorShift {
+            // xorshift is stuck at 0 forever if seeded with 0.
+            state: if seed == 0 { 1 } else { seed },
+        }
+    }
 
+    fn seed_from_time() -> u32 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.subsec_nanos())
+            .unwrap_or(1)
+    }
+
+    fn next(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+
+    fn range(&mut self, min: u32, max: u32) -> u32 {
+        min + (self.next() % (max - min))
+    }
+}
+
+// === Leaderboard ===
+
+const LEADERBOARD_VERSION: u32 = 1;
+const LEADERBOARD_SIZE: usize = 5;
+
+#[derive(Serialize, Deserialize)]
+struct LeaderboardEntry {
+    score: i32,
+    seed: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Leaderboard {
+    version: u32,
+    entries: Vec<LeaderboardEntry>,
+}
+
+impl Leaderboard {
+    fn empty() -> Leaderboard {
+        Leaderboard {
+            version: LEADERBOARD_VERSION,
+            entries: Vec::new(),
+        }
+    }
+
+    // Missing/corrupt save data just means a first run - start fresh.
+    fn load() -> Leaderboard {
+        Leaderboard::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_else(Leaderboard::empty)
+    }
+
+    fn path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("flappy-in-rust").join("leaderboard.json"))
+    }
+
+    fn best_score(&self) -> Option<i32> {
+        self.entries.iter().map(|entry| entry.score).max()
+    }
+
+    fn qualifies(&self, score: i32) -> bool {
+        score > 0
+            && (self.entries.len() < LEADERBOARD_SIZE
+                || self.entries.iter().any(|entry| score > entry.score))
+    }
+
+    fn submit(&mut self, score: i32, seed: u32) {
+        self.entries.push(LeaderboardEntry { score, seed });
+        self.entries.sort_by(|a, b| b.score.cmp(&a.score));
+        self.entries.truncate(LEADERBOARD_SIZE);
+        self.save();
+    }
+
+    fn save(&self) {
+        let path = match Leaderboard::path() {
+            Some(path) => path,
+            None => return,
+        };
+
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+
+        if let Ok(json) = serde_json::to_string_pretty(self) {
+            let _ = fs::write(path, json);
+        }
+    }
+}
+
+// === Screen Scaler ===
+
+#[derive(Clone, Copy, PartialEq)]
+enum ScaleMode {
+    PixelPerfect,
+    Smooth,
+}
+
+// Renders the game into a fixed-size logical canvas, then blits that
+// canvas onto the real backbuffer scaled (and letterboxed) to fit
+// whatever size the window actually is.
+struct ScreenScaler {
+    canvas: Canvas,
+    mode: ScaleMode,
+    screen_rect: Rectangle,
+}
+
+impl ScreenScaler {
+    fn new(ctx: &mut Context, mode: ScaleMode) -> tetra::Result<ScreenScaler> {
+        let canvas = Canvas::new(ctx, SCREEN_WIDTH, SCREEN_HEIGHT)?;
+        canvas.texture().set_filter_mode(ctx, ScreenScaler::filter_mode(mode));
+
+        let mut scaler = ScreenScaler {
+            canvas,
+            mode,
+            screen_rect: Rectangle::new(0.0, 0.0, SCREEN_WIDTH as f32, SCREEN_HEIGHT as f32),
+        };
+        scaler.recalculate(window::get_width(ctx), window::get_height(ctx));
+        Ok(scaler)
+    }
+
+    fn filter_mode(mode: ScaleMode) -> FilterMode {
+        match mode {
+            ScaleMode::PixelPerfect => FilterMode::Nearest,
+            ScaleMode::Smooth => FilterMode::Linear,
+        }
+    }
+
+    // Lets the player flip between crisp integer scaling and a smooth
+    // stretch-to-fit at runtime, e.g. bound to a debug/options key.
+    fn toggle_mode(&mut self, ctx: &mut Context, window_width: i32, window_height: i32) {
+        self.mode = match self.mode {
+            ScaleMode::PixelPerfect => ScaleMode::Smooth,
+            ScaleMode::Smooth => ScaleMode::PixelPerfect,
+        };
+        self.canvas.texture().set_filter_mode(ctx, ScreenScaler::filter_mode(self.mode));
+        self.recalculate(window_width, window_height);
+    }
+
+    fn recalculate(&mut self, window_width: i32, window_height: i32) {
+        let window_width = window_width as f32;
+        let window_height = window_height as f32;
+
+        let mut scale = (window_width / SCREEN_WIDTH as f32).min(window_height / SCREEN_HEIGHT as f32);
+        if self.mode == ScaleMode::PixelPerfect {
+            scale = scale.floor().max(1.0);
+        }
+
+        let width = SCREEN_WIDTH as f32 * scale;
+        let height = SCREEN_HEIGHT as f32 * scale;
+
+        self.screen_rect = Rectangle::new(
+            (window_width - width) / 2.0,
+            (window_height - height) / 2.0,
+            width,
+            height,
+        );
+    }
+
+    fn begin(&self, ctx: &mut Context) {
+        graphics::set_canvas(ctx, &self.canvas);
+        graphics::clear(ctx, Color::BLACK);
+    }
+
+    fn end(&self, ctx: &mut Context) {
+        graphics::reset_canvas(ctx);
+        graphics::clear(ctx, Color::BLACK);
+
+        let scale = self.screen_rect.width / SCREEN_WIDTH as f32;
+        graphics::draw(
+            ctx,
+            self.canvas.texture(),
+            DrawParams::new()
+                .position(Vec2::new(self.screen_rect.x, self.screen_rect.y))
+                .scale(Vec2::new(scale, scale)),
+        );
+    }
+
+    // Maps a point in window space (e.g. from the OS mouse cursor) back
+    // into the logical 288x505 canvas, so hit-testing keeps working no
+    // matter how the window has been resized.
+    fn project_mouse(&self, window_point: Vec2) -> Vec2 {
+        let scale = self.screen_rect.width / SCREEN_WIDTH as f32;
+        Vec2::new(
+            (window_point.x - self.screen_rect.x) / scale,
+            (window_point.y - self.screen_rect.y) / scale,
+        )
+    }
+}
+
+// === Input ===
+
+// Abstracts over the different ways a player can flap/confirm, so scenes
+// don't have to know whether the press came from a mouse click, a key, or
+// a gamepad.
+trait InputController {
+    fn flap_pressed(&mut self, ctx: &mut Context) -> bool;
+    fn confirm_pressed(&mut self, ctx: &mut Context) -> bool;
+}
+
+// `is_mouse_button_down` reports a held state, not an edge - track the
+// previous frame ourselves so a single click only fires once.
+struct MouseController {
+    is_down: bool,
+}
+
+impl MouseController {
+    fn new() -> MouseController {
+        MouseController { is_down: false }
+    }
+
+    fn pressed(&mut self, ctx: &mut Context) -> bool {
+        let down = input::is_mouse_button_down(ctx, MouseButton::Left);
+        let just_pressed = down && !self.is_down;
+        self.is_down = down;
+        just_pressed
+    }
+}
+
+impl InputController for MouseController {
+    fn flap_pressed(&mut self, ctx: &mut Context) -> bool {
+        self.pressed(ctx)
+    }
+
+    fn confirm_pressed(&mut self, ctx: &mut Context) -> bool {
+        self.pressed(ctx)
+    }
+}
+
+struct KeyboardController;
+
+impl InputController for KeyboardController {
+    fn flap_pressed(&mut self, ctx: &mut Context) -> bool {
+        input::is_key_pressed(ctx, Key::Space) || input::is_key_pressed(ctx, Key::Up)
+    }
+
+    fn confirm_pressed(&mut self, ctx: &mut Context) -> bool {
+        self.flap_pressed(ctx)
+    }
+}
+
+// The left stick is a held axis rather than an edge - track the previous
+// frame the same way `MouseController` does for the left button.
+struct GamepadController {
+    stick_was_up: bool,
+}
+
+impl GamepadController {
+    fn new() -> GamepadController {
+        GamepadController { stick_was_up: false }
+    }
+
+    fn stick_pressed(&mut self, ctx: &mut Context) -> bool {
+        let up = input::get_gamepad_axis_position(ctx, 0, GamepadAxis::LeftStickY) < -0.5;
+        let just_pressed = up && !self.stick_was_up;
+        self.stick_was_up = up;
+        just_pressed
+    }
+}
+
+impl InputController for GamepadController {
+    fn flap_pressed(&mut self, ctx: &mut Context) -> bool {
+        let button = input::is_gamepad_button_pressed(ctx, 0, GamepadButton::A);
+        // `||` would short-circuit and skip the stick's own edge tracking.
+        button | self.stick_pressed(ctx)
+    }
+
+    fn confirm_pressed(&mut self, ctx: &mut Context) -> bool {
+        self.flap_pressed(ctx)
+    }
+}
+
+// Combines several controllers so a scene can accept whichever device the
+// player happens to be using without caring which one it was.
+struct CompositeController {
+    controllers: Vec<Box<dyn InputController>>,
+}
+
+impl CompositeController {
+    fn new(controllers: Vec<Box<dyn InputController>>) -> CompositeController {
+        CompositeController { controllers }
+    }
+}
+
+impl InputController for CompositeController {
+    fn flap_pressed(&mut self, ctx: &mut Context) -> bool {
+        // `||` would short-circuit and leave the later controllers' own
+        // edge-detection stale, so every one of them sees this frame.
+        self.controllers
+            .iter_mut()
+            .fold(false, |pressed, controller| controller.flap_pressed(ctx) | pressed)
+    }
+
+    fn confirm_pressed(&mut self, ctx: &mut Context) -> bool {
+        self.controllers
+            .iter_mut()
+            .fold(false, |pressed, controller| controller.confirm_pressed(ctx) | pressed)
+    }
 }
 
 // === Scene Management ===
 
 trait Scene {
-    fn update(&mut self, ctx: &mut Context) -> tetra::Result<Transition>;
+    fn update(&mut self, ctx: &mut Context, scaler: &ScreenScaler) -> tetra::Result<Transition>;
     fn draw(&mut self, ctx: &mut Context, dt: f64);
 }
 
@@ -42,6 +459,12 @@ enum Transition {
     None,
     Push(Box<dyn Scene>),
     Pop,
+    Replace(Box<dyn Scene>),
+    // Like `Replace`, but also drops the scene beneath the current one
+    // first - e.g. retrying from a `GameOverScene` should discard both
+    // itself and the dead `GameScene` it was pushed on top of, not just
+    // swap itself out and leave the old game underneath.
+    ReplaceParent(Box<dyn Scene>),
 }
 
 // Boxing/dynamic dispatch could be avoided here by defining an enum for all
@@ -49,23 +472,31 @@ enum Transition {
 
 struct SceneManager {
     scenes: Vec<Box<dyn Scene>>,
+    scaler: ScreenScaler,
 }
 
 impl SceneManager {
     fn new(ctx: &mut Context) -> tetra::Result<SceneManager> {
-        let initial_scene = TitleScene::new(ctx)?;
-        graphics::set_scaling(ctx, ScreenScaling::ShowAllPixelPerfect);
+        let leaderboard = Rc::new(RefCell::new(Leaderboard::load()));
+        let initial_scene = TitleScene::new(ctx, leaderboard)?;
+        let scaler = ScreenScaler::new(ctx, ScaleMode::PixelPerfect)?;
         window::show_mouse(ctx);
         Ok(SceneManager {
             scenes: vec![Box::new(initial_scene)],
+            scaler,
         })
     }
 }
 
 impl State for SceneManager {
     fn update(&mut self, ctx: &mut Context) -> tetra::Result {
+        // F1 flips the scaler between pixel-perfect and a smooth stretch.
+        if input::is_key_pressed(ctx, Key::F1) {
+            self.scaler.toggle_mode(ctx, window::get_width(ctx), window::get_height(ctx));
+        }
+
         match self.scenes.last_mut() {
-            Some(active_scene) => match active_scene.update(ctx)? {
+            Some(active_scene) => match active_scene.update(ctx, &self.scaler)? {
                 Transition::None => {}
                 Transition::Push(s) => {
                     self.scenes.push(s);
@@ -73,6 +504,15 @@ impl State for SceneManager {
                 Transition::Pop => {
                     self.scenes.pop();
                 }
+                Transition::Replace(s) => {
+                    self.scenes.pop();
+                    self.scenes.push(s);
+                }
+                Transition::ReplaceParent(s) => {
+                    self.scenes.pop();
+                    self.scenes.pop();
+                    self.scenes.push(s);
+                }
             },
             None => window::quit(ctx),
         }
@@ -81,11 +521,23 @@ impl State for SceneManager {
     }
 
     fn draw(&mut self, ctx: &mut Context, dt: f64) -> tetra::Result {
+        self.scaler.begin(ctx);
+
         match self.scenes.last_mut() {
             Some(active_scene) => active_scene.draw(ctx, dt),
             None => window::quit(ctx),
         }
 
+        self.scaler.end(ctx);
+
+        Ok(())
+    }
+
+    fn event(&mut self, ctx: &mut Context, event: Event) -> tetra::Result {
+        if let Event::Resized { width, height } = event {
+            self.scaler.recalculate(width, height);
+        }
+
         Ok(())
     }
 }
@@ -128,63 +580,156 @@ impl Background {
         self.cloud_rect.x += 1.0 ;
     }
 
-    fn draw(&mut self, ctx: &mut Context) {
+    fn draw(&mut self, ctx: &mut Context, offset: Vec2) {
         graphics::draw(ctx, &self.cloud_texture,
             DrawParams::new()
-            .position(Vec2::new(0.0, 300.0))
+            .position(Vec2::new(0.0, 300.0) + offset)
             .clip(self.cloud_rect));
-    
+
         graphics::draw(ctx, &self.cityscape_texture,
             DrawParams::new()
-            .position(Vec2::new(0.0, 330.0))
+            .position(Vec2::new(0.0, 330.0) + offset)
             .clip(self.cityscape_rect));
-    
+
 
         graphics::draw(ctx, &self.forest_texture,
             DrawParams::new()
-            .position(Vec2::new(0.0, 360.0))
+            .position(Vec2::new(0.0, 360.0) + offset)
             .clip(self.forest_rect));
-    
+
         graphics::draw(ctx, &self.ground_texture,
             DrawParams::new()
-            .position(Vec2::new(0.0, 400.0))
+            .position(Vec2::new(0.0, 400.0) + offset)
             .clip(self.ground_rect));
     }
 }
 
+// === Camera ===
+
+// How far the camera is allowed to drift from center while following.
+// `Frame::offset` narrows this further per direction so the parallax
+// layers never show empty space past the play field's edge.
+const FRAME_MAX_PAN: f32 = 12.0;
+const FRAME_FOLLOW_SPEED: f32 = 0.1;
+
+struct Frame {
+    position: Vec2,
+    target: Vec2,
+
+    shake_intensity: f32,
+    shake_duration: f32,
+    shake_remaining: f32,
+    shake_offset: Vec2,
+}
+
+impl Frame {
+    fn new() -> Frame {
+        Frame {
+            position: Vec2::new(0.0, 0.0),
+            target: Vec2::new(0.0, 0.0),
+            shake_intensity: 0.0,
+            shake_duration: 0.0,
+            shake_remaining: 0.0,
+            shake_offset: Vec2::new(0.0, 0.0),
+        }
+    }
+
+    fn follow(&mut self, target: Vec2) {
+        self.target = target;
+    }
+
+    fn shake(&mut self, intensity: f32, duration: f32) {
+        self.shake_intensity = intensity;
+        self.shake_duration = duration;
+        self.shake_remaining = duration;
+    }
+
+    fn update(&mut self, rng: &mut XorShift, delta: f32) {
+        self.position.x += (self.target.x - self.position.x) * FRAME_FOLLOW_SPEED;
+        self.position.y += (self.target.y - self.position.y) * FRAME_FOLLOW_SPEED;
+        self.position.x = self.position.x.max(-FRAME_MAX_PAN).min(FRAME_MAX_PAN);
+        self.position.y = self.position.y.max(-FRAME_MAX_PAN).min(FRAME_MAX_PAN);
+
+        if self.shake_remaining > 0.0 {
+            self.shake_remaining = (self.shake_remaining - delta).max(0.0);
+            let intensity = self.shake_intensity * (self.shake_remaining / self.shake_duration);
+            let range = (intensity * 2.0).max(1.0) as u32;
+            self.shake_offset = Vec2::new(
+                rng.range(0, range) as f32 - intensity,
+                rng.range(0, range) as f32 - intensity,
+            );
+        } else {
+            self.shake_offset = Vec2::new(0.0, 0.0);
+        }
+    }
+
+    // Shifting right would uncover the layers' left edge, and lifting the
+    // 112px-tall ground (drawn at `GROUND_Y`) too far would uncover the
+    // bottom of the play field, so those two directions are narrowed.
+    fn offset(&self) -> Vec2 {
+        let offset = self.position + self.shake_offset;
+        Vec2::new(
+            offset.x.max(-FRAME_MAX_PAN).min(0.0),
+            offset.y.max(SCREEN_HEIGHT as f32 - (GROUND_Y + 112.0)).min(FRAME_MAX_PAN),
+        )
+    }
+}
+
 // === Title Scene ===
 
 struct TitleScene {
     sky_texture: Texture,
     title: Texture,
-    start: Texture, 
+    start: Texture,
     bird: Animation,
     background: Background,
     start_rect: Rectangle,
+    seed: u32,
+    seed_text: Text,
+    leaderboard: Rc<RefCell<Leaderboard>>,
+    best_score_text: Option<Text>,
+
+    mouse: MouseController,
+    controller: CompositeController,
 }
 
 impl TitleScene {
-    fn new(ctx: &mut Context) -> tetra::Result<TitleScene> {
+    fn new(ctx: &mut Context, leaderboard: Rc<RefCell<Leaderboard>>) -> tetra::Result<TitleScene> {
         let button_texture = Texture::new(ctx, "./resources/start-button.png")?;
         let start_rect = Rectangle::new(
-            SCREEN_WIDTH as f32/2.0 - button_texture.width() as f32 / 2.0, 
+            SCREEN_WIDTH as f32/2.0 - button_texture.width() as f32 / 2.0,
             300.0 - button_texture.height() as f32 / 2.0,
             button_texture.width() as f32,
-            button_texture.height() as f32    
+            button_texture.height() as f32
         );
+        let seed = XorShift::seed_from_time();
+        let best_score_text = leaderboard
+            .borrow()
+            .best_score()
+            .map(|score| Text::new(format!("Best: {}", score), Font::default(), 16.0));
 
         Ok(TitleScene {
             sky_texture: Texture::new(ctx, "./resources/sky.png")?,
             title: Texture::new(ctx, "./resources/title.png")?,
             start: button_texture,
-            
+
             bird: Animation::new(
                 Texture::new(ctx, "./resources/bird.png")?,
                 Rectangle::row(0.0, 0.0, 34.0, 24.0).take(3).collect(),
                 5,
             ),
             background: Background::new(ctx)?,
-            start_rect: start_rect
+            start_rect: start_rect,
+            seed,
+            seed_text: Text::new(format!("Seed: {}", seed), Font::default(), 16.0),
+            leaderboard,
+            best_score_text,
+
+            mouse: MouseController::new(),
+            controller: CompositeController::new(vec![
+                Box::new(KeyboardController),
+                Box::new(GamepadController::new()),
+            ]),
         })
     }
 
@@ -193,19 +738,41 @@ impl TitleScene {
         point.x <= (self.start_rect.x + self.start_rect.width) &&
         point.y >= self.start_rect.y &&
         point.y <= (self.start_rect.y + self.start_rect.height)
-           
+
+    }
+
+    // Lets the player dial in a known seed (e.g. one shared for a
+    // speedrun) instead of always getting a fresh one from the clock.
+    // Uses Left/Right rather than Up/Down so seed adjustment never shares
+    // a key with the confirm/flap controls below.
+    fn adjust_seed(&mut self, ctx: &mut Context) {
+        if input::is_key_pressed(ctx, Key::Right) {
+            self.seed = self.seed.wrapping_add(1);
+            self.seed_text.set_content(format!("Seed: {}", self.seed));
+        } else if input::is_key_pressed(ctx, Key::Left) {
+            self.seed = self.seed.wrapping_sub(1);
+            self.seed_text.set_content(format!("Seed: {}", self.seed));
+        }
     }
 }
 
 impl Scene for TitleScene {
 
-    fn update(&mut self, ctx: &mut Context) -> tetra::Result<Transition> {
+    fn update(&mut self, ctx: &mut Context, scaler: &ScreenScaler) -> tetra::Result<Transition> {
         self.bird.tick();
         self.background.update();
-
-        let mouse_position = input::get_mouse_position(ctx);
-        if input::is_mouse_button_down(ctx, MouseButton::Left) &&  self.button_contains(mouse_position) {
-            Ok(Transition::Push(Box::new(GameScene::new(ctx)?)))
+        self.adjust_seed(ctx);
+
+        let mouse_position = scaler.project_mouse(input::get_mouse_position(ctx));
+        let clicked_start = self.mouse.confirm_pressed(ctx) && self.button_contains(mouse_position);
+
+        // Key/pad players can start without ever aiming at the button.
+        if clicked_start || self.controller.confirm_pressed(ctx) {
+            Ok(Transition::Push(Box::new(GameScene::with_seed(
+                ctx,
+                self.seed,
+                self.leaderboard.clone(),
+            )?)))
         } else {
             Ok(Transition::None)
         }
@@ -214,15 +781,92 @@ impl Scene for TitleScene {
     fn draw(&mut self, ctx: &mut Context, _dt: f64) {
         graphics::draw(ctx, &self.sky_texture, Vec2::new(0.0, 0.0));
 
-        self.background.draw(ctx);
+        self.background.draw(ctx, Vec2::new(0.0, 0.0));
 
         graphics::draw(ctx, &self.bird, Vec2::new(230.0,105.0));
 
         graphics::draw(ctx, &self.title, Vec2::new(30.0, 100.0));
         graphics::draw(ctx, &self.start, Vec2::new(self.start_rect.x, self.start_rect.y));
+        graphics::draw(ctx, &self.seed_text, Vec2::new(10.0, SCREEN_HEIGHT as f32 - 26.0));
+
+        if let Some(best_score_text) = &self.best_score_text {
+            graphics::draw(ctx, best_score_text, Vec2::new(30.0, 180.0));
+        }
+    }
+}
+
+// === Pipes ===
+
+const PIPE_WIDTH: f32 = 52.0;
+const PIPE_GAP: f32 = 100.0;
+const PIPE_SPEED: f32 = 2.0;
+const PIPE_SPAWN_INTERVAL: i32 = 90;
+const GROUND_Y: f32 = 400.0;
+
+struct Pipe {
+    x: f32,
+    gap_y: f32,
+    scored: bool,
+}
+
+impl Pipe {
+    fn new(x: f32, gap_y: f32) -> Pipe {
+        Pipe {
+            x,
+            gap_y,
+            scored: false,
+        }
+    }
+
+    fn center_x(&self) -> f32 {
+        self.x + PIPE_WIDTH / 2.0
+    }
+
+    fn top_rect(&self) -> Rectangle {
+        Rectangle::new(self.x, 0.0, PIPE_WIDTH, self.gap_y - PIPE_GAP / 2.0)
+    }
+
+    fn bottom_rect(&self) -> Rectangle {
+        let bottom_y = self.gap_y + PIPE_GAP / 2.0;
+        Rectangle::new(self.x, bottom_y, PIPE_WIDTH, GROUND_Y - bottom_y)
+    }
+
+    fn update(&mut self) {
+        self.x -= PIPE_SPEED;
+    }
+
+    fn off_screen(&self) -> bool {
+        self.x + PIPE_WIDTH < 0.0
+    }
+
+    fn draw(&self, ctx: &mut Context, texture: &Texture, offset: Vec2) {
+        let top_rect = self.top_rect();
+        let bottom_rect = self.bottom_rect();
+
+        // The pipe texture is a single upright segment; stretch and flip it
+        // to cover however tall the top/bottom obstacle needs to be.
+        graphics::draw(
+            ctx,
+            texture,
+            DrawParams::new()
+                .position(Vec2::new(self.x, top_rect.height) + offset)
+                .scale(Vec2::new(1.0, -top_rect.height / texture.height() as f32)),
+        );
+
+        graphics::draw(
+            ctx,
+            texture,
+            DrawParams::new()
+                .position(Vec2::new(self.x, bottom_rect.y) + offset)
+                .scale(Vec2::new(1.0, bottom_rect.height / texture.height() as f32)),
+        );
     }
 }
 
+fn rects_overlap(a: &Rectangle, b: &Rectangle) -> bool {
+    a.x < b.x + b.width && a.x + a.width > b.x && a.y < b.y + b.height && a.y + a.height > b.y
+}
+
 // === Game Scene ===
 
 struct GameScene {
@@ -233,7 +877,13 @@ struct GameScene {
     get_ready: Texture,
 
     bird: Animation,
-    
+
+    pipe_texture: Texture,
+    pipes: Vec<Pipe>,
+    rng: XorShift,
+    seed: u32,
+    leaderboard: Rc<RefCell<Leaderboard>>,
+
     flap_sound: Sound,
     ground_hit_sound: Sound,
     pipe_hit_sound: Sound,
@@ -248,27 +898,55 @@ struct GameScene {
     rotation: f32,
     position: Vec2,
     velocity: Vec2,
-    flap_counter: i32,
-    flap_delta: f64,
-    is_mouse_down: bool,
+    controller: CompositeController,
     instructions_visible: bool,
+    instructions_alpha: f32,
     allow_gravity: bool,
+
+    tween_manager: TweenManager,
+    flap_tween: Option<Rc<RefCell<Tween<f32>>>>,
+    instructions_fade: Option<Rc<RefCell<Tween<f32>>>>,
+
+    frame: Frame,
+    // Set on collision, counts down to zero before the scene actually
+    // transitions to `GameOverScene` - keeps the scene drawn (and the
+    // screen-shake visible) for the duration of the shake instead of
+    // cutting away the instant the bird dies.
+    dying_timer: Option<f32>,
 }
 
+// How long the scene stays on-screen, frozen, after a fatal collision -
+// matches the `frame.shake` duration so the shake always finishes playing.
+const DYING_DURATION: f32 = 0.3;
+
 impl GameScene {
-    fn new(ctx: &mut Context) -> tetra::Result<GameScene> {
+    fn new(ctx: &mut Context, leaderboard: Rc<RefCell<Leaderboard>>) -> tetra::Result<GameScene> {
+        GameScene::with_seed(ctx, XorShift::seed_from_time(), leaderboard)
+    }
+
+    fn with_seed(
+        ctx: &mut Context,
+        seed: u32,
+        leaderboard: Rc<RefCell<Leaderboard>>,
+    ) -> tetra::Result<GameScene> {
         Ok(GameScene {
             sky_texture: Texture::new(ctx, "./resources/sky.png")?,
             background: Background::new(ctx)?,
             get_ready: Texture::new(ctx, "./resources/get-ready.png")?,
             instructions: Texture::new(ctx, "./resources/instructions.png")?,
-            
+
             bird: Animation::new(
                 Texture::new(ctx, "./resources/bird.png")?,
                 Rectangle::row(0.0, 0.0, 34.0, 24.0).take(3).collect(),
                 5,
             ),
 
+            pipe_texture: Texture::new(ctx, "./resources/pipe.png")?,
+            pipes: Vec::new(),
+            rng: XorShift::new(seed),
+            seed,
+            leaderboard,
+
             flap_sound: Sound::new("./resources/flap.wav")?,
             ground_hit_sound: Sound::new("./resources/ground-hit.wav")?,
             pipe_hit_sound: Sound::new("./resources/pipe-hit.wav")?,
@@ -282,160 +960,301 @@ impl GameScene {
             rotation: 0.0,
             position: Vec2::new(100.0, SCREEN_HEIGHT as f32/2.0),
             velocity: Vec2::new(0.0, 0.0),
-            flap_counter: 0,
-            flap_delta: 0.0,
-            is_mouse_down: false,
+            controller: CompositeController::new(vec![
+                Box::new(MouseController::new()),
+                Box::new(KeyboardController),
+                Box::new(GamepadController::new()),
+            ]),
             instructions_visible: true,
+            instructions_alpha: 1.0,
             allow_gravity: false,
+
+            tween_manager: TweenManager::new(),
+            flap_tween: None,
+            instructions_fade: None,
+
+            frame: Frame::new(),
+            dying_timer: None,
         })
     }
 
     fn start_game(&mut self) {
-        if self.instructions_visible {
-            self.instructions_visible = false;
+        if self.instructions_visible && self.instructions_fade.is_none() {
+            let fade = Rc::new(RefCell::new(Tween::new(1.0, 0.0, 0.3, Easing::QuadOut)));
+            self.tween_manager.add(fade.clone());
+            self.instructions_fade = Some(fade);
         }
         self.allow_gravity = true;
     }
 
     fn flap(&mut self) {
         self.velocity.y = -7.5;
-        self.flap_counter = 6;
-        self.tween_rotation();
-    }
-
-    fn tween_rotation(&mut self) {
-        let distance = (-1.0 - self.rotation) as f64;
-        self.flap_delta = distance.abs() / self.flap_counter as f64;
-    }
-
-    // fn collides(&mut self, move_x: i32, move_y: i32) -> bool {
-    //     for (x, y) in self.block.segments() {
-    //         let new_x = x + move_x;
-    //         let new_y = y + move_y;
-
-    //         if new_y < 0 {
-    //             continue;
-    //         }
-
-    //         if new_x < 0
-    //             || new_x > 9
-    //             || new_y > 21
-    //             || self.board[new_y as usize][new_x as usize].is_some()
-    //         {
-    //             return true;
-    //         }
-    //     }
-
-    //     false
-    // }
-
-    // fn lock(&mut self) {
-    //     let color = self.block.color();
-
-    //     for (x, y) in self.block.segments() {
-    //         if x >= 0 && x <= 9 && y >= 0 && y <= 21 {
-    //             self.board[y as usize][x as usize] = Some(color);
-    //         }
-    //     }
-    // }
-
-    // fn check_for_clears(&mut self) -> bool {
-    //     let mut cleared = false;
-
-    //     'outer: for y in 0..22 {
-    //         for x in 0..10 {
-    //             if self.board[y][x].is_none() {
-    //                 continue 'outer;
-    //             }
-    //         }
-
-    //         cleared = true;
-
-    //         self.score += 1;
-    //         self.score_text
-    //             .set_content(format!("Score: {}", self.score));
-
-    //         for clear_y in (0..=y).rev() {
-    //             if clear_y > 0 {
-    //                 self.board[clear_y] = self.board[clear_y - 1];
-    //             } else {
-    //                 self.board[clear_y] = [None; 10];
-    //             }
-    //         }
-    //     }
-
-    //     cleared
-    // }
-
-    // fn check_for_game_over(&self) -> bool {
-    //     self.board[0].iter().any(Option::is_some) || self.board[1].iter().any(Option::is_some)
-    // }
-
-    // fn board_blocks(&self) -> impl Iterator<Item = (i32, i32, Color)> + '_ {
-    //     self.board.iter().enumerate().flat_map(|(y, row)| {
-    //         row.iter()
-    //             .enumerate()
-    //             .filter(|(_, segment)| segment.is_some())
-    //             .map(move |(x, segment)| (x as i32, y as i32, segment.unwrap()))
-    //     })
-    // }
+
+        let tween = Rc::new(RefCell::new(Tween::new(self.rotation, -1.0, 0.2, Easing::ElasticOut)));
+        self.tween_manager.add(tween.clone());
+        self.flap_tween = Some(tween);
+    }
+
+    fn spawn_pipe(&mut self) {
+        let gap_y = self.rng.range(80, (GROUND_Y - 80.0) as u32) as f32;
+        self.pipes.push(Pipe::new(SCREEN_WIDTH as f32, gap_y));
+    }
+
+    fn bird_rect(&self) -> Rectangle {
+        Rectangle::new(self.position.x - 17.0, self.position.y - 12.0, 34.0, 24.0)
+    }
+
+    fn collides(&self) -> bool {
+        let bird_rect = self.bird_rect();
+
+        if bird_rect.y + bird_rect.height >= GROUND_Y {
+            return true;
+        }
+
+        self.pipes.iter().any(|pipe| {
+            rects_overlap(&bird_rect, &pipe.top_rect()) || rects_overlap(&bird_rect, &pipe.bottom_rect())
+        })
+    }
 }
 
 impl Scene for GameScene {
-    fn update(&mut self, ctx: &mut Context) -> tetra::Result<Transition> {
+    fn update(&mut self, ctx: &mut Context, _scaler: &ScreenScaler) -> tetra::Result<Transition> {
+        // After a fatal collision, keep the scene on-screen (frozen, still
+        // shaking) for `DYING_DURATION` instead of pushing `GameOverScene`
+        // on the same frame the shake starts - otherwise the shake is
+        // computed but never drawn.
+        if let Some(remaining) = self.dying_timer {
+            let delta = time::get_delta_time(ctx).as_secs_f64() as f32;
+            self.frame.update(&mut self.rng, delta);
+            self.background.update();
+
+            let remaining = remaining - delta;
+            if remaining <= 0.0 {
+                return Ok(Transition::Push(Box::new(GameOverScene::new(
+                    ctx,
+                    self.score,
+                    self.seed,
+                    self.leaderboard.clone(),
+                )?)));
+            }
+
+            self.dying_timer = Some(remaining);
+            return Ok(Transition::None);
+        }
+
         self.bird.tick();
 
-        if input::is_mouse_button_down(ctx, MouseButton::Left) {
-            if !self.is_mouse_down {
-                if self.instructions_visible {
-                    self.start_game();
-                }
-                self.flap();
-                self.is_mouse_down = true;
+        if self.controller.flap_pressed(ctx) {
+            if self.instructions_visible {
+                self.start_game();
+            }
+            self.flap();
+        }
+
+        let delta = time::get_delta_time(ctx).as_secs_f64();
+        self.tween_manager.update(delta);
+
+        if let Some(fade) = self.instructions_fade.clone() {
+            self.instructions_alpha = fade.borrow().value();
+            if fade.borrow_mut().is_complete() {
+                self.instructions_fade = None;
+                self.instructions_visible = false;
+                self.instructions_alpha = 0.0;
             }
-        } else {
-            self.is_mouse_down = false;
         }
 
         if self.allow_gravity {
             self.velocity.y = self.velocity.y + GRAVITY / 30.0;
             self.position.y = self.position.y + self.velocity.y;
 
-            if self.flap_counter > 0 {
-                self.rotation -= self.flap_delta as f32;
-                self.flap_counter -= 1; 
-            } if self.rotation < 1.3 {
+            if let Some(tween) = self.flap_tween.clone() {
+                self.rotation = tween.borrow().value();
+                if tween.borrow_mut().is_complete() {
+                    self.flap_tween = None;
+                }
+            } else if self.rotation < 1.3 {
                 self.rotation += 0.05;
             }
+
+            self.move_timer += 1;
+            if self.move_timer >= PIPE_SPAWN_INTERVAL {
+                self.move_timer = 0;
+                self.spawn_pipe();
+            }
+
+            for pipe in self.pipes.iter_mut() {
+                pipe.update();
+
+                if !pipe.scored && self.position.x > pipe.center_x() {
+                    pipe.scored = true;
+                    self.score += 1;
+                    self.score_text.set_content(format!("Score: {}", self.score));
+                    self.score_sound.play(ctx)?;
+                }
+            }
+            self.pipes.retain(|pipe| !pipe.off_screen());
+
+            if self.collides() {
+                self.allow_gravity = false;
+                self.velocity = Vec2::new(0.0, 0.0);
+                self.frame.shake(4.0, DYING_DURATION);
+                self.dying_timer = Some(DYING_DURATION);
+
+                if self.bird_rect().y + self.bird_rect().height >= GROUND_Y {
+                    self.ground_hit_sound.play(ctx)?;
+                } else {
+                    self.pipe_hit_sound.play(ctx)?;
+                }
+
+                return Ok(Transition::None);
+            }
+
+            self.frame.follow(Vec2::new(0.0, (self.position.y - SCREEN_HEIGHT as f32 / 2.0) * 0.2));
         }
 
+        self.frame.update(&mut self.rng, delta as f32);
         self.background.update();
 
         Ok(Transition::None)
     }
 
     fn draw(&mut self, ctx: &mut Context, _dt: f64) {
+        let offset = self.frame.offset();
+
         graphics::clear(ctx, Color::rgb(0.392, 0.584, 0.929));
         graphics::draw(ctx, &self.sky_texture, Vec2::new(0.0, 0.0));
 
-        self.background.draw(ctx);
+        self.background.draw(ctx, offset);
+
+        for pipe in self.pipes.iter() {
+            pipe.draw(ctx, &self.pipe_texture, offset);
+        }
 
         if self.instructions_visible {
+            let fade_color = Color::rgba(1.0, 1.0, 1.0, self.instructions_alpha);
+
             graphics::draw(ctx, &self.instructions, DrawParams::new()
-                .position(Vec2::new(SCREEN_WIDTH as f32/2.0, 325.0))
-                .origin(Vec2::new(self.instructions.width() as f32/2.0,self.instructions.height() as f32/2.0)));
+                .position(Vec2::new(SCREEN_WIDTH as f32/2.0, 325.0) + offset)
+                .origin(Vec2::new(self.instructions.width() as f32/2.0,self.instructions.height() as f32/2.0))
+                .color(fade_color));
             graphics::draw(ctx, &self.get_ready, DrawParams::new()
-                .position(Vec2::new(SCREEN_WIDTH as f32/2.0, 100.0))
-                .origin(Vec2::new(self.get_ready.width() as f32/2.0,self.get_ready.height() as f32/2.0)));
+                .position(Vec2::new(SCREEN_WIDTH as f32/2.0, 100.0) + offset)
+                .origin(Vec2::new(self.get_ready.width() as f32/2.0,self.get_ready.height() as f32/2.0))
+                .color(fade_color));
         }
 
         graphics::draw(
             ctx,
             &self.bird,
             DrawParams::new()
-                .position(self.position)
+                .position(self.position + offset)
                 .origin(Vec2::new(17.0, 12.0))
                 .rotation(self.rotation)
         );
+
+        if !self.instructions_visible {
+            graphics::draw(ctx, &self.score_text, Vec2::new(10.0, 10.0));
+        }
+    }
+}
+
+// === Game Over Scene ===
+
+struct GameOverScene {
+    sky_texture: Texture,
+    background: Background,
+    game_over: Texture,
+    retry: Texture,
+    retry_rect: Rectangle,
+    final_score_text: Text,
+    leaderboard: Rc<RefCell<Leaderboard>>,
+    // Kept so Retry can start a fresh `GameScene` with the same seed -
+    // a seeded run should stay replayable across a death, not just up
+    // to the first one.
+    seed: u32,
+
+    mouse: MouseController,
+    controller: CompositeController,
+}
+
+impl GameOverScene {
+    fn new(
+        ctx: &mut Context,
+        score: i32,
+        seed: u32,
+        leaderboard: Rc<RefCell<Leaderboard>>,
+    ) -> tetra::Result<GameOverScene> {
+        let retry_texture = Texture::new(ctx, "./resources/start-button.png")?;
+        let retry_rect = Rectangle::new(
+            SCREEN_WIDTH as f32 / 2.0 - retry_texture.width() as f32 / 2.0,
+            340.0 - retry_texture.height() as f32 / 2.0,
+            retry_texture.width() as f32,
+            retry_texture.height() as f32,
+        );
+
+        if leaderboard.borrow().qualifies(score) {
+            leaderboard.borrow_mut().submit(score, seed);
+        }
+
+        Ok(GameOverScene {
+            sky_texture: Texture::new(ctx, "./resources/sky.png")?,
+            background: Background::new(ctx)?,
+            game_over: Texture::new(ctx, "./resources/game-over.png")?,
+            retry: retry_texture,
+            retry_rect,
+            final_score_text: Text::new(format!("Score: {}", score), Font::default(), 16.0),
+            leaderboard,
+            seed,
+
+            mouse: MouseController::new(),
+            controller: CompositeController::new(vec![
+                Box::new(KeyboardController),
+                Box::new(GamepadController::new()),
+            ]),
+        })
+    }
+
+    fn button_contains(&self, point: Vec2) -> bool {
+        point.x >= self.retry_rect.x
+            && point.x <= (self.retry_rect.x + self.retry_rect.width)
+            && point.y >= self.retry_rect.y
+            && point.y <= (self.retry_rect.y + self.retry_rect.height)
+    }
+}
+
+impl Scene for GameOverScene {
+    fn update(&mut self, ctx: &mut Context, scaler: &ScreenScaler) -> tetra::Result<Transition> {
+        self.background.update();
+
+        let mouse_position = scaler.project_mouse(input::get_mouse_position(ctx));
+        let clicked_retry = self.mouse.confirm_pressed(ctx) && self.button_contains(mouse_position);
+
+        // Key/pad players can retry without ever aiming at the button.
+        if clicked_retry || self.controller.confirm_pressed(ctx) {
+            // Drops both this scene and the dead `GameScene` underneath it
+            // instead of leaving the old game on the stack, and keeps the
+            // same seed so the run stays replayable after a retry.
+            return Ok(Transition::ReplaceParent(Box::new(GameScene::with_seed(
+                ctx,
+                self.seed,
+                self.leaderboard.clone(),
+            )?)));
+        }
+
+        Ok(Transition::None)
+    }
+
+    fn draw(&mut self, ctx: &mut Context, _dt: f64) {
+        graphics::draw(ctx, &self.sky_texture, Vec2::new(0.0, 0.0));
+
+        self.background.draw(ctx, Vec2::new(0.0, 0.0));
+
+        graphics::draw(ctx, &self.game_over, DrawParams::new()
+            .position(Vec2::new(SCREEN_WIDTH as f32 / 2.0, 200.0))
+            .origin(Vec2::new(self.game_over.width() as f32 / 2.0, self.game_over.height() as f32 / 2.0)));
+
+        graphics::draw(ctx, &self.final_score_text, Vec2::new(SCREEN_WIDTH as f32 / 2.0 - 30.0, 260.0));
+
+        graphics::draw(ctx, &self.retry, Vec2::new(self.retry_rect.x, self.retry_rect.y));
     }
 }